@@ -6,7 +6,7 @@ use std::{
     mem::{size_of, MaybeUninit},
     os::{
         raw::{c_void, c_int, c_ulong, c_long, c_char},
-        unix::{fs::OpenOptionsExt, io::IntoRawFd},
+        unix::{fs::OpenOptionsExt, io::{IntoRawFd, RawFd}},
     },
     ptr::null_mut,
     pin::Pin,
@@ -17,6 +17,8 @@ use std::{
 };
 use pix::rgb::SRgba8;
 use pix::Raster;
+use jpeg_decoder::Decoder as JpegDecoder;
+use jpeg_decoder::PixelFormat;
 
 #[repr(C)]
 struct InotifyEv {
@@ -234,6 +236,7 @@ enum V4l2Memory {
     Mmap = 1,
     UserPtr = 2,
     MemoryOverlay = 3,
+    DmaBuf = 4,
 }
 
 #[repr(C)]
@@ -244,6 +247,66 @@ struct V4l2RequestBuffers {
     reserved: [u32; 2],
 }
 
+#[repr(C)]
+struct V4l2FmtDesc {
+    index: u32,          /* Format number */
+    type_: V4l2BufType,  /* buffer type */
+    flags: u32,
+    description: [u8; 32], /* Description string */
+    pixelformat: u32,    /* Format fourcc */
+    reserved: [u32; 4],
+}
+
+#[repr(C)]
+struct V4l2QueryCtrl {
+    id: u32,
+    type_: u32, /* enum v4l2_ctrl_type, checked against the V4L2_CTRL_TYPE_* consts */
+    name: [u8; 32],
+    minimum: i32,
+    maximum: i32,
+    step: i32,
+    default_value: i32,
+    flags: u32,
+    reserved: [u32; 2],
+}
+
+#[repr(C)]
+struct V4l2Control {
+    id: u32,
+    value: i32,
+}
+
+#[repr(C)]
+struct V4l2ExportBuffer {
+    type_: V4l2BufType,
+    index: u32,
+    plane: u32,
+    flags: u32,
+    fd: i32,
+    reserved: [u32; 11],
+}
+
+#[repr(C)]
+#[derive(Copy, Clone)]
+struct V4l2Fract {
+    numerator: u32,
+    denominator: u32,
+}
+
+#[repr(C)]
+struct V4l2CropCap {
+    type_: V4l2BufType,
+    bounds: V4l2Rect,
+    defrect: V4l2Rect,
+    pixelaspect: V4l2Fract,
+}
+
+#[repr(C)]
+struct V4l2Crop {
+    type_: V4l2BufType,
+    c: V4l2Rect,
+}
+
 /// IOCTL
 const fn iow_v(size: usize, num: u8) -> c_ulong {
     (0x80 << 24) | ((size as c_ulong & 0x1fff) << 16) | ((b'V' as c_ulong) << 8) | num as c_ulong
@@ -262,12 +325,41 @@ const VIDIOC_REQBUFS: c_ulong = iowr_v(size_of::<V4l2RequestBuffers>(), 8);
 const VIDIOC_QUERYBUF: c_ulong = iowr_v(size_of::<V4l2Buffer>(), 9);
 const VIDIOC_QBUF: c_ulong = iowr_v(size_of::<V4l2Buffer>(), 15);
 const VIDIOC_DQBUF: c_ulong = iowr_v(size_of::<V4l2Buffer>(), 17);
+const VIDIOC_ENUM_FMT: c_ulong = iowr_v(size_of::<V4l2FmtDesc>(), 2);
+const VIDIOC_G_CTRL: c_ulong = iowr_v(size_of::<V4l2Control>(), 27);
+const VIDIOC_S_CTRL: c_ulong = iowr_v(size_of::<V4l2Control>(), 28);
+const VIDIOC_QUERYCTRL: c_ulong = iowr_v(size_of::<V4l2QueryCtrl>(), 36);
+const VIDIOC_EXPBUF: c_ulong = iowr_v(size_of::<V4l2ExportBuffer>(), 16);
+const VIDIOC_CROPCAP: c_ulong = iowr_v(size_of::<V4l2CropCap>(), 58);
+const VIDIOC_S_CROP: c_ulong = iow_v(size_of::<V4l2Crop>(), 60);
 
 const fn v4l2_fourcc(a: &[u8; 4]) -> u32 {
     ((a[0] as u32)<<0)|((a[1] as u32)<<8)|((a[2] as u32)<<16)|((a[3] as u32)<<24)
 }
 
 const V4L2_PIX_FMT_MJPEG: u32 = v4l2_fourcc(b"MJPG");
+const V4L2_PIX_FMT_YUYV: u32 = v4l2_fourcc(b"YUYV");
+
+/// First id of the standard "user" camera controls (brightness, contrast, ...).
+const V4L2_CID_BASE: u32 = 0x00980900;
+/// First id of the "Camera Controls" class (exposure/focus/zoom/pan/tilt),
+/// where most UVC webcams put their real exposure and gain controls.
+const V4L2_CID_CAMERA_CLASS_BASE: u32 = 0x009A0900;
+/// Set when the driver exposes a control id but it isn't currently usable.
+const V4L2_CTRL_FLAG_DISABLED: u32 = 0x0001;
+
+const V4L2_CTRL_TYPE_INTEGER: u32 = 1;
+const V4L2_CTRL_TYPE_BOOLEAN: u32 = 2;
+const V4L2_CTRL_TYPE_MENU: u32 = 3;
+
+/// Default size of the mmap'd capture buffer ring, so the driver can keep
+/// filling the next buffer while we're still decoding the last.
+const CAMERA_BUF_QUEUE_SIZE: u32 = 3;
+
+/// Device supports the `VIDIOC_REQBUFS`/`VIDIOC_STREAMON` mmap path.
+const V4L2_CAP_STREAMING: u32 = 0x0400_0000;
+/// Device supports plain `read()`/`write()` capture.
+const V4L2_CAP_READWRITE: u32 = 0x0100_0000;
 
 const PROT_READ: c_int = 0x04;
 const PROT_WRITE: c_int = 0x02;
@@ -302,11 +394,41 @@ extern "C" {
     fn inotify_add_watch(fd: c_int, path: *const c_char, mask: u32) -> c_int;
 }
 
-/// 
+///
 pub enum Event {
     Connect(Box<Camera>),
 }
 
+/// The kind of value a [`Control`] holds.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ControlKind {
+    /// A ranged integer value.
+    Integer,
+    /// An on/off value.
+    Boolean,
+    /// One of a fixed set of named values.
+    Menu,
+}
+
+/// A single camera control, i.e. brightness, contrast, exposure, or gain.
+#[derive(Debug, Clone)]
+pub struct Control {
+    /// Driver-assigned id, pass to [`Camera::get_control`]/[`Camera::set_control`].
+    pub id: u32,
+    /// Human-readable name, i.e. "Brightness".
+    pub name: String,
+    /// Kind of value this control holds.
+    pub kind: ControlKind,
+    /// Smallest value accepted by this control.
+    pub minimum: i32,
+    /// Largest value accepted by this control.
+    pub maximum: i32,
+    /// Step size between valid values.
+    pub step: i32,
+    /// Value the driver initializes this control to.
+    pub default: i32,
+}
+
 /// All cameras / webcams that are connected to the operating system.
 pub struct Rig {
     device: Device,
@@ -391,7 +513,7 @@ impl Future for Rig {
                             }
                         };
                         self.connected.insert(file);
-                        if let Some(camera) = Camera::new(fd.into_raw_fd(), Raster::with_clear(640, 480)) {
+                        if let Some(camera) = Camera::new(fd.into_raw_fd(), Raster::with_clear(640, 480), CAMERA_BUF_QUEUE_SIZE, false) {
                             return Poll::Ready(
                                 camera
                             );
@@ -431,25 +553,41 @@ impl Drop for Rig {
     }
 }
 
+// How frames are pulled off the device, chosen in `Camera::new` from the
+// capabilities `VIDIOC_QUERYCAP` reports.
+enum CaptureMethod {
+    // VIDIOC_REQBUFS/VIDIOC_STREAMON mmap ring (V4L2_CAP_STREAMING).
+    Streaming {
+        buf: V4l2Buffer,
+        buffers: Vec<(*mut c_void, u32)>, // mmap'd (ptr, length), indexed by v4l2 buffer index
+        // Dma-buf fd exported for each mmap'd buffer, indexed the same way;
+        // empty unless `Camera::new` was asked to export buffers.
+        dmabuf_fds: Vec<RawFd>,
+    },
+    // Plain read() capture, for devices that only advertise
+    // V4L2_CAP_READWRITE (e.g. sn9c102 without streaming support).
+    Read {
+        buffer: Vec<u8>,
+    },
+}
+
 /// A camera / webcam in the `Rig`.
 pub struct Camera {
     // Camera device to watch for events.
     device: Device,
 
 	// Linux specific
-	buffer: *mut c_void,
-	buf: V4l2Buffer,
+	capture: CaptureMethod,
+
+	// Negotiated capture pixel format (MJPEG or YUYV fourcc).
+	pixel_format: u32,
 
-	// 
-	data: *mut c_void, // JPEG file data
-	size: u32, // Size of JPEG file
-	
 	// SRGB camera frame data.
 	raster: Raster<SRgba8>,
 }
 
 impl Camera {
-    pub fn new(fd: c_int, raster: Raster<SRgba8>) -> Option<Camera> {
+    pub fn new(fd: c_int, raster: Raster<SRgba8>, buffer_count: u32, export_dmabuf: bool) -> Option<Camera> {
 	    // Open the device
         let filename = "/dev/video0";
         let fd = match OpenOptions::new()
@@ -474,6 +612,46 @@ impl Camera {
 	    if xioctl(fd, VIDIOC_QUERYCAP, caps.as_mut_ptr().cast()) == -1 {
 		    panic!("Failed Querying Capabilites\n");
 	    }
+	    let caps = unsafe { caps.assume_init() };
+	    let can_stream = caps.capabilities & V4L2_CAP_STREAMING != 0;
+	    let can_read = caps.capabilities & V4L2_CAP_READWRITE != 0;
+	    if !can_stream && !can_read {
+		    unsafe { close(fd) };
+		    return None;
+	    }
+
+	    // Enumerate the pixel formats the device advertises, preferring
+	    // hardware-compressed MJPEG and falling back to raw YUYV (which we
+	    // convert to SRGB in software) for devices that don't offer MJPEG.
+	    let mut supports_mjpeg = false;
+	    let mut supports_yuyv = false;
+	    let mut desc_index = 0;
+	    loop {
+		    let mut desc = V4l2FmtDesc {
+		        index: desc_index,
+		        type_: V4l2BufType::VideoCapture,
+		        flags: 0,
+		        description: [0; 32],
+		        pixelformat: 0,
+		        reserved: [0; 4],
+		    };
+		    if xioctl(fd, VIDIOC_ENUM_FMT, (&mut desc as *mut V4l2FmtDesc).cast()) == -1 {
+			    break; // EINVAL: no more formats to enumerate.
+		    }
+		    match desc.pixelformat {
+		        V4L2_PIX_FMT_MJPEG => supports_mjpeg = true,
+		        V4L2_PIX_FMT_YUYV => supports_yuyv = true,
+		        _ => {}
+		    }
+		    desc_index += 1;
+	    }
+	    let pixel_format = if supports_mjpeg {
+		    V4L2_PIX_FMT_MJPEG
+	    } else if supports_yuyv {
+		    V4L2_PIX_FMT_YUYV
+	    } else {
+		    V4L2_PIX_FMT_MJPEG
+	    };
 
 	    // Set image format.
 	    let mut fmt = V4l2Format {
@@ -482,7 +660,7 @@ impl Camera {
 	            pix: V4l2PixFormat {
             	    width: 0, // w,
 	                height: 0, // h,
-	                pixelformat: V4L2_PIX_FMT_MJPEG,
+	                pixelformat: pixel_format,
 	                field: V4l2Field::None,
                     bytesperline: 0,
                     sizeimage: 0,
@@ -495,16 +673,40 @@ impl Camera {
 	    if xioctl(fd, VIDIOC_S_FMT, (&mut fmt as *mut V4l2Format).cast()) == -1 {
 		    panic!("Error setting Pixel Format\n");
 	    }
+	    let sizeimage = unsafe { fmt.fmt.pix.sizeimage };
+
+	    // We asked for whatever resolution the driver defaults to (width/
+	    // height 0), so read back what it actually picked and resize the
+	    // raster to match before we ever blit a decoded frame into it.
+	    let (width, height) = unsafe { (fmt.fmt.pix.width, fmt.fmt.pix.height) };
+	    let raster = if raster.width() == width && raster.height() == height {
+		    raster
+	    } else {
+		    Raster::with_clear(width, height)
+	    };
 
-	    // Request a video capture buffer.
+	    // No mmap streaming support: fall back to read()-based capture.
+	    if !can_stream {
+		    return Some(Camera {
+		        device: Device::new(fd, Watcher::new().input()),
+		        capture: CaptureMethod::Read {
+			        buffer: vec![0u8; sizeimage as usize],
+		        },
+		        pixel_format,
+		        raster,
+		    });
+	    }
+
+	    // Request a ring of video capture buffers, so the driver can keep
+	    // filling the next buffer while we're still decoding the last one.
 	    let mut req = V4l2RequestBuffers {
-	        count: 1,
+	        count: buffer_count,
 	        type_: V4l2BufType::VideoCapture,
 	        memory: V4l2Memory::Mmap,
 	        reserved: [0; 2],
 	    };
 
-	     
+
 	    if xioctl(fd, VIDIOC_REQBUFS, (&mut req as *mut V4l2RequestBuffers).cast()) == -1 {
 		    panic!("Error Requesting Buffer\n");
 	    }
@@ -537,66 +739,328 @@ impl Camera {
             reserved: 0,
 	    };
 
-	    if xioctl(fd, VIDIOC_QUERYBUF, (&mut buf as *mut V4l2Buffer).cast()) == -1 {
-		    panic!("Error Querying Buffer\n");
-	    }
-        // FIXME: Raster
-	    // unsafe { *output = mmap(null_mut(), buf.length.try_into().unwrap(), PROT_READ | PROT_WRITE, MAP_SHARED,
-		//    fd, buf.m.offset.try_into().unwrap()) };
+	    // Map and queue every buffer the driver actually gave us (it may
+	    // grant fewer than `req.count`).
+	    let mut buffers = Vec::with_capacity(req.count as usize);
+	    let mut dmabuf_fds = Vec::with_capacity(if export_dmabuf { req.count as usize } else { 0 });
+	    for index in 0..req.count {
+		    buf.index = index;
+		    if xioctl(fd, VIDIOC_QUERYBUF, (&mut buf as *mut V4l2Buffer).cast()) == -1 {
+			    panic!("Error Querying Buffer\n");
+		    }
 
-	    // Start the capture:
-	    if xioctl(fd, VIDIOC_QBUF, (&mut buf as *mut V4l2Buffer).cast()) == -1 {
-		    panic!("Error: VIDIOC_QBUF");
+		    // Map the driver's buffer into our address space.
+		    let buffer = unsafe {
+		        mmap(
+		            null_mut(),
+		            buf.length.try_into().unwrap(),
+		            PROT_READ | PROT_WRITE,
+		            MAP_SHARED,
+		            fd,
+		            buf.m.offset.try_into().unwrap(),
+		        )
+		    };
+		    if buffer as isize == -1 {
+			    panic!("Error mmap\n");
+		    }
+		    buffers.push((buffer, buf.length));
+
+		    // Opt-in: export this mmap'd buffer as a dma-buf fd so a GPU
+		    // or compositor can import it without the CPU decode copy.
+		    if export_dmabuf {
+			    let mut exp = V4l2ExportBuffer {
+			        type_: V4l2BufType::VideoCapture,
+			        index,
+			        plane: 0,
+			        flags: 0,
+			        fd: -1,
+			        reserved: [0; 11],
+			    };
+			    if xioctl(fd, VIDIOC_EXPBUF, (&mut exp as *mut V4l2ExportBuffer).cast()) == -1 {
+				    panic!("Error: VIDIOC_EXPBUF");
+			    }
+			    dmabuf_fds.push(exp.fd as RawFd);
+		    }
+
+		    // Start the capture:
+		    if xioctl(fd, VIDIOC_QBUF, (&mut buf as *mut V4l2Buffer).cast()) == -1 {
+			    panic!("Error: VIDIOC_QBUF");
+		    }
 	    }
 
 	    let mut type_ = V4l2BufType::VideoCapture;
 	    if xioctl(fd, VIDIOC_STREAMON, (&mut type_ as *mut V4l2BufType).cast()) == -1 {
 		    panic!("Error: VIDIOC_STREAMON");
 	    }
-	    
+
 	    Some(Camera {
 	        device: Device::new(fd, Watcher::new().input()),
-	        size: buf.length,
-	        buf,
-	        buffer: null_mut(),
-	        data: null_mut(),
+	        capture: CaptureMethod::Streaming {
+		        buf,
+		        buffers,
+		        dmabuf_fds,
+	        },
+	        pixel_format,
 	        raster,
 	    })
     }
+
+    /// Get the most recently decoded camera frame.
+    pub fn frame(&self) -> &Raster<SRgba8> {
+        &self.raster
+    }
+
+    /// Get the dma-buf fd for the most-recently-dequeued buffer, for
+    /// zero-copy hand-off to a GPU or compositor. Only valid if `new` was
+    /// called with `export_dmabuf` set.
+    ///
+    /// The fd is borrowed: it stays owned by this `Camera` (closed in
+    /// `Drop`) and remains valid for the buffer's lifetime in the ring, so
+    /// callers must `dup(2)` it before handing it to another process or
+    /// holding onto it past the next `poll`, and must not close it.
+    pub fn export_frame(&self) -> RawFd {
+        match &self.capture {
+            CaptureMethod::Streaming { buf, dmabuf_fds, .. } => {
+                if dmabuf_fds.is_empty() {
+                    panic!("export_frame: Camera::new was not called with export_dmabuf set");
+                }
+                dmabuf_fds[buf.index as usize]
+            }
+            CaptureMethod::Read { .. } => panic!("export_frame: no dma-buf in read() capture mode"),
+        }
+    }
+
+    // Decode a MJPEG-encoded frame and blit it into `self.raster`.
+    fn decode_mjpeg(&mut self, jpeg: &[u8]) {
+        let mut decoder = JpegDecoder::new(jpeg);
+        let pixels = match decoder.decode() {
+            Ok(pixels) => pixels,
+            Err(_e) => return, // Corrupt/truncated frame, drop it.
+        };
+        let info = match decoder.info() {
+            Some(info) => info,
+            None => return,
+        };
+        let out = self.raster.pixels_mut();
+        match info.pixel_format {
+            PixelFormat::RGB24 => {
+	            for (i, rgb) in pixels.chunks_exact(3).enumerate() {
+	                if i >= out.len() {
+	                    break;
+	                }
+	                out[i] = SRgba8::new(rgb[0], rgb[1], rgb[2], 255);
+	            }
+            }
+            PixelFormat::L8 => {
+	            for (i, &y) in pixels.iter().enumerate() {
+	                if i >= out.len() {
+	                    break;
+	                }
+	                out[i] = SRgba8::new(y, y, y, 255);
+	            }
+            }
+            // CMYK32 and other scans aren't supported yet; drop the frame
+            // rather than reinterpreting their bytes as RGB24.
+            _ => {}
+        }
+    }
+
+    // Convert a raw YUYV (Y0 U Y1 V macropixel) frame to SRGB and blit it
+    // into `self.raster`, using the standard BT.601 inverse transform.
+    fn convert_yuyv(&mut self, yuyv: &[u8]) {
+        let out = self.raster.pixels_mut();
+        let mut i = 0;
+        for macropixel in yuyv.chunks_exact(4) {
+            let y0 = macropixel[0] as f32;
+            let u = macropixel[1] as f32 - 128.0;
+            let y1 = macropixel[2] as f32;
+            let v = macropixel[3] as f32 - 128.0;
+            for y in [y0, y1] {
+                if i >= out.len() {
+                    return;
+                }
+                let r = (y + 1.402 * v).clamp(0.0, 255.0) as u8;
+                let g = (y - 0.344 * u - 0.714 * v).clamp(0.0, 255.0) as u8;
+                let b = (y + 1.772 * u).clamp(0.0, 255.0) as u8;
+                out[i] = SRgba8::new(r, g, b, 255);
+                i += 1;
+            }
+        }
+    }
+
+    /// Enumerate the controls (brightness, contrast, exposure, ...) this
+    /// camera exposes. Walks both the standard "User Controls" class and
+    /// the "Camera Controls" class, since UVC webcams commonly expose
+    /// their real exposure/focus/zoom controls only in the latter.
+    pub fn controls(&self) -> Vec<Control> {
+        let mut controls = Vec::new();
+        let ids = (V4L2_CID_BASE..(V4L2_CID_BASE + 64))
+            .chain(V4L2_CID_CAMERA_CLASS_BASE..(V4L2_CID_CAMERA_CLASS_BASE + 64));
+        for id in ids {
+            let mut query = V4l2QueryCtrl {
+                id,
+                type_: 0,
+                name: [0; 32],
+                minimum: 0,
+                maximum: 0,
+                step: 0,
+                default_value: 0,
+                flags: 0,
+                reserved: [0; 2],
+            };
+            if xioctl(self.device.fd(), VIDIOC_QUERYCTRL, (&mut query as *mut V4l2QueryCtrl).cast()) == -1 {
+                continue; // No control at this id.
+            }
+            if query.flags & V4L2_CTRL_FLAG_DISABLED != 0 {
+                continue;
+            }
+            let kind = match query.type_ {
+                V4L2_CTRL_TYPE_INTEGER => ControlKind::Integer,
+                V4L2_CTRL_TYPE_BOOLEAN => ControlKind::Boolean,
+                V4L2_CTRL_TYPE_MENU => ControlKind::Menu,
+                _ => continue, // Buttons, classes, strings, etc. aren't exposed yet.
+            };
+            let nul = query.name.iter().position(|&b| b == 0).unwrap_or(query.name.len());
+            let name = String::from_utf8_lossy(&query.name[..nul]).into_owned();
+            controls.push(Control {
+                id: query.id,
+                name,
+                kind,
+                minimum: query.minimum,
+                maximum: query.maximum,
+                step: query.step,
+                default: query.default_value,
+            });
+        }
+        controls
+    }
+
+    /// Get the current value of a control by id (see [`Camera::controls`]).
+    pub fn get_control(&self, id: u32) -> i32 {
+        let mut ctrl = V4l2Control { id, value: 0 };
+        if xioctl(self.device.fd(), VIDIOC_G_CTRL, (&mut ctrl as *mut V4l2Control).cast()) == -1 {
+            panic!("Error: VIDIOC_G_CTRL");
+        }
+        ctrl.value
+    }
+
+    /// Set the value of a control by id (see [`Camera::controls`]).
+    pub fn set_control(&self, id: u32, value: i32) {
+        let mut ctrl = V4l2Control { id, value };
+        if xioctl(self.device.fd(), VIDIOC_S_CTRL, (&mut ctrl as *mut V4l2Control).cast()) == -1 {
+            panic!("Error: VIDIOC_S_CTRL");
+        }
+    }
+
+    /// Select a region of interest to capture from the sensor, clamped to
+    /// the bounds `VIDIOC_CROPCAP` reports. Sensors that don't support
+    /// cropping silently keep capturing the full frame.
+    pub fn set_crop(&self, left: i32, top: i32, width: i32, height: i32) {
+        let mut cap = V4l2CropCap {
+            type_: V4l2BufType::VideoCapture,
+            bounds: V4l2Rect { left: 0, top: 0, width: 0, height: 0 },
+            defrect: V4l2Rect { left: 0, top: 0, width: 0, height: 0 },
+            pixelaspect: V4l2Fract { numerator: 1, denominator: 1 },
+        };
+        if xioctl(self.device.fd(), VIDIOC_CROPCAP, (&mut cap as *mut V4l2CropCap).cast()) == -1 {
+            if errno() != /*EINVAL*/22 {
+                panic!("Error: VIDIOC_CROPCAP");
+            }
+            return; // EINVAL: sensor doesn't support cropping at all.
+        }
+
+        let bounds = cap.bounds;
+        let left = left.clamp(bounds.left, bounds.left + bounds.width);
+        let top = top.clamp(bounds.top, bounds.top + bounds.height);
+        let width = width.clamp(0, bounds.left + bounds.width - left);
+        let height = height.clamp(0, bounds.top + bounds.height - top);
+
+        let mut crop = V4l2Crop {
+            type_: V4l2BufType::VideoCapture,
+            c: V4l2Rect { left, top, width, height },
+        };
+        if xioctl(self.device.fd(), VIDIOC_S_CROP, (&mut crop as *mut V4l2Crop).cast()) == -1 {
+            if errno() != /*EINVAL*/22 {
+                panic!("Error: VIDIOC_S_CROP");
+            }
+            // EINVAL: sensor doesn't support this crop rectangle, ignore.
+        }
+    }
 }
 
 impl Future for Camera {
     type Output = ();
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
-	    if xioctl(self.device.fd(), VIDIOC_DQBUF, (&mut self.buf as *mut V4l2Buffer).cast()) == -1 {
-	        let errno = errno();
-		    if errno == /*EAGAIN*/11 {
-		        self.device.register_waker(cx.waker());
-		        return Poll::Pending;
-	        }
-	        unsafe {
-    		    close(self.device.fd());
+	    // Fill (or dequeue) a buffer, getting back a raw pointer + length so
+	    // decoding below doesn't have to juggle a live borrow of `capture`.
+	    let (ptr, len): (*const u8, usize) = match &mut self.capture {
+		    CaptureMethod::Streaming { buf, buffers, .. } => {
+			    if xioctl(self.device.fd(), VIDIOC_DQBUF, (buf as *mut V4l2Buffer).cast()) == -1 {
+			        let errno = errno();
+				    if errno == /*EAGAIN*/11 {
+				        self.device.register_waker(cx.waker());
+				        return Poll::Pending;
+			        }
+			        unsafe {
+	    			    close(self.device.fd());
+				    }
+				    panic!("Error retrieving frame {}\n", errno);
+			    }
+			    let (buffer, _) = buffers[buf.index as usize];
+			    (buffer as *const u8, buf.bytesused as usize)
 		    }
-		    panic!("Error retrieving frame {}\n", errno);
+		    CaptureMethod::Read { buffer } => {
+			    let n = unsafe {
+			        read(self.device.fd(), buffer.as_mut_ptr().cast(), buffer.len())
+			    };
+			    if n < 0 {
+				    let errno = errno();
+				    if errno == /*EAGAIN*/11 {
+				        self.device.register_waker(cx.waker());
+				        return Poll::Pending;
+			        }
+				    panic!("Error reading frame {}\n", errno);
+			    }
+			    (buffer.as_ptr(), n as usize)
+		    }
+	    };
+
+	    // Decode the filled buffer into the output raster, dispatching on
+	    // the pixel format negotiated in `Camera::new`.
+	    let data = unsafe { std::slice::from_raw_parts(ptr, len) };
+	    match self.pixel_format {
+	        V4L2_PIX_FMT_YUYV => self.convert_yuyv(data),
+	        _ => self.decode_mjpeg(data),
 	    }
 
-	    if xioctl(self.device.fd(), VIDIOC_QBUF, (&mut self.buf as *mut V4l2Buffer).cast()) == -1 {
-		    panic!("VIDIOC_QBUF");
+	    if let CaptureMethod::Streaming { buf, .. } = &mut self.capture {
+		    if xioctl(self.device.fd(), VIDIOC_QBUF, (buf as *mut V4l2Buffer).cast()) == -1 {
+			    panic!("VIDIOC_QBUF");
+		    }
 	    }
-	    
+
 	    Poll::Ready(())
     }
 }
 
 impl Drop for Camera {
     fn drop(&mut self) {
-	    let mut type_ = V4l2BufType::VideoCapture;
-	    if xioctl(self.device.fd(), VIDIOC_STREAMOFF, (&mut type_ as *mut V4l2BufType).cast()) == -1 {
-		    panic!("Error VIDIOC_STREAMOFF");
-	    }
-	    if unsafe { munmap(self.buffer, self.size.try_into().unwrap()) == -1 } {
-		    panic!("Error munmap");
+	    if let CaptureMethod::Streaming { buffers, dmabuf_fds, .. } = &self.capture {
+		    let mut type_ = V4l2BufType::VideoCapture;
+		    if xioctl(self.device.fd(), VIDIOC_STREAMOFF, (&mut type_ as *mut V4l2BufType).cast()) == -1 {
+			    panic!("Error VIDIOC_STREAMOFF");
+		    }
+		    for &(buffer, length) in buffers {
+			    if unsafe { munmap(buffer, length.try_into().unwrap()) == -1 } {
+				    panic!("Error munmap");
+			    }
+		    }
+		    for &fd in dmabuf_fds {
+			    if unsafe { close(fd) == -1 } {
+				    panic!("Error closing dma-buf fd");
+			    }
+		    }
 	    }
 	    if unsafe { close(self.device.fd()) == -1 }  {
 		    panic!("Error close");